@@ -0,0 +1,184 @@
+//! Remembering the tests that passed, so that grading an unchanged submission
+//! again skips them.
+//!
+//! The results are kept in `.autograder/cache/results.json` under the current
+//! directory, along with a fingerprint of the source files they were graded
+//! against. The source files are those git would commit, as listed by
+//! `git ls-files`, or every file outside a git repository, leaving out `.git`
+//! and the cache itself. Changing, adding, or removing any of them starts the
+//! cache over. Within one set of files, each result is keyed on a hash of what
+//! else decides it, such as the test's definition and the config.
+//!
+//! The hashes are 64-bit FNV-1a, which is simple enough to stay the same across
+//! Rust versions, unlike the standard library's hasher.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Where the cache is kept, relative to the directory being graded
+pub const CACHE_FILE: &str = ".autograder/cache/results.json";
+
+#[derive(Serialize, Deserialize)]
+struct CacheFile<T> {
+    /// The fingerprint of the source files the results were graded against
+    tree: String,
+    results: BTreeMap<String, T>,
+}
+
+/// The results recorded for the current source files
+pub struct Cache<T> {
+    path: PathBuf,
+    file: CacheFile<T>,
+}
+
+impl<T: Serialize + DeserializeOwned> Cache<T> {
+    /// Reads the cache of the directory. Results recorded for other source
+    /// files, or in a file that can't be read, are dropped.
+    pub fn open(root: &Path) -> io::Result<Self> {
+        let path = root.join(CACHE_FILE);
+        let tree = fingerprint(root)?;
+        let results = fs::read_to_string(&path)
+            .ok()
+            .and_then(|text| serde_json::from_str::<CacheFile<T>>(&text).ok())
+            .filter(|file| file.tree == tree)
+            .map(|file| file.results)
+            .unwrap_or_default();
+        Ok(Self {
+            path,
+            file: CacheFile { tree, results },
+        })
+    }
+
+    pub fn get(&self, key: &str) -> Option<&T> {
+        self.file.results.get(key)
+    }
+
+    pub fn insert(&mut self, key: String, result: T) {
+        self.file.results.insert(key, result);
+    }
+
+    pub fn save(&self) -> io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        fs::write(&self.path, serde_json::to_string(&self.file)?)
+    }
+}
+
+/// Hashes the parts as one, keeping `["ab", "c"]` apart from `["a", "bc"]`
+pub fn hash(parts: &[&[u8]]) -> String {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for part in parts {
+        for byte in (part.len() as u64).to_le_bytes().iter().chain(part.iter()) {
+            hash ^= u64::from(*byte);
+            hash = hash.wrapping_mul(0x100000001b3);
+        }
+    }
+    format!("{:016x}", hash)
+}
+
+/// Hashes the paths and contents of the source files under the directory
+fn fingerprint(root: &Path) -> io::Result<String> {
+    let mut files = match git_files(root) {
+        Some(files) => files,
+        None => {
+            let mut files = vec![];
+            walk(root, Path::new(""), &mut files)?;
+            files
+        }
+    };
+    files.sort();
+    let mut parts = vec![];
+    for file in files.iter() {
+        // A file that git still tracks may have been deleted
+        let contents = fs::read(root.join(file)).unwrap_or_default();
+        parts.push(hash(&[file.to_string_lossy().as_bytes(), &contents]));
+    }
+    let parts: Vec<_> = parts.iter().map(String::as_bytes).collect();
+    Ok(hash(&parts))
+}
+
+/// The files git would commit, or `None` outside a git repository
+fn git_files(root: &Path) -> Option<Vec<PathBuf>> {
+    let output = Command::new("git")
+        .args([
+            "ls-files",
+            "-z",
+            "--cached",
+            "--others",
+            "--exclude-standard",
+        ])
+        .current_dir(root)
+        .stdin(Stdio::null())
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|output| output.status.success())?;
+    let files = output
+        .stdout
+        .split(|byte| *byte == 0)
+        .map(|file| PathBuf::from(String::from_utf8_lossy(file).into_owned()))
+        .filter(|file| !file.as_os_str().is_empty() && !is_skipped(file))
+        .collect();
+    Some(files)
+}
+
+/// Collects the files under `root.join(dir)` that aren't skipped, relative to `root`
+fn walk(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> io::Result<()> {
+    for entry in fs::read_dir(root.join(dir))? {
+        let entry = entry?;
+        let path = dir.join(entry.file_name());
+        if is_skipped(&path) {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            walk(root, &path, files)?;
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether the path is in git's own directory or the cache's, which change on
+/// their own
+fn is_skipped(path: &Path) -> bool {
+    matches!(
+        path.iter().next().and_then(|part| part.to_str()),
+        Some(".git" | ".autograder")
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn changing_a_source_file_clears_the_cache() {
+        let root = env::temp_dir().join(format!("autograder-cache-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::write(root.join("src/main.c"), "int main() {}").unwrap();
+        let mut cache = Cache::open(&root).unwrap();
+        cache.insert("test".to_string(), 3);
+        cache.save().unwrap();
+        assert_eq!(Cache::<i32>::open(&root).unwrap().get("test"), Some(&3));
+
+        fs::write(root.join("src/main.c"), "int main() { return 0; }").unwrap();
+        assert_eq!(Cache::<i32>::open(&root).unwrap().get("test"), None);
+
+        // Hidden files, such as a config under .github, count too
+        let mut cache = Cache::open(&root).unwrap();
+        cache.insert("test".to_string(), 3);
+        cache.save().unwrap();
+        fs::create_dir_all(root.join(".github")).unwrap();
+        fs::write(root.join(".github/expected.txt"), "hello").unwrap();
+        assert_eq!(Cache::<i32>::open(&root).unwrap().get("test"), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -0,0 +1,126 @@
+//! Line-by-line differences between the expected and actual output.
+//!
+//! The diff is the longest common subsequence of lines, so unchanged lines are
+//! kept in place and everything else shows as removed from the expected output
+//! or added by the program.
+
+use std::fmt::Write;
+
+/// Beyond this many line pairs, the outputs are shown as wholly replaced rather
+/// than spending quadratic time and memory on an exact diff
+const MAX_CELLS: usize = 4_000_000;
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Change<'a> {
+    Equal(&'a str),
+    /// A line of the expected output that the program did not print
+    Delete(&'a str),
+    /// A line the program printed that was not expected
+    Insert(&'a str),
+}
+
+/// Diffs the lines of the expected and actual output
+pub fn diff_lines<'a>(expected: &'a str, actual: &'a str) -> Vec<Change<'a>> {
+    let old: Vec<&str> = expected.lines().collect();
+    let new: Vec<&str> = actual.lines().collect();
+    if old.len().saturating_mul(new.len()) > MAX_CELLS {
+        return old
+            .iter()
+            .map(|line| Change::Delete(line))
+            .chain(new.iter().map(|line| Change::Insert(line)))
+            .collect();
+    }
+
+    // lengths[i][j] is the length of the longest common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+
+    let mut changes = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            changes.push(Change::Equal(old[i]));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            changes.push(Change::Delete(old[i]));
+            i += 1;
+        } else {
+            changes.push(Change::Insert(new[j]));
+            j += 1;
+        }
+    }
+    changes.extend(old[i..].iter().map(|line| Change::Delete(line)));
+    changes.extend(new[j..].iter().map(|line| Change::Insert(line)));
+    changes
+}
+
+/// Renders a diff as text, marking removed lines with `-` and added lines with `+`
+pub fn render(changes: &[Change<'_>]) -> String {
+    let mut out = String::new();
+    for change in changes {
+        // Writing to a String can't fail
+        let _ = match change {
+            Change::Equal(line) => writeln!(out, "  {}", line),
+            Change::Delete(line) => writeln!(out, "- {}", line),
+            Change::Insert(line) => writeln!(out, "+ {}", line),
+        };
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Change::*;
+
+    #[test]
+    fn lines_are_inserted_deleted_and_replaced() {
+        assert_eq!(
+            diff_lines("a\nc\n", "a\nb\nc\n"),
+            [Equal("a"), Insert("b"), Equal("c")]
+        );
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nc"),
+            [Equal("a"), Delete("b"), Equal("c")]
+        );
+        assert_eq!(
+            diff_lines("a\nb\nc", "a\nx\nc"),
+            [Equal("a"), Delete("b"), Insert("x"), Equal("c")]
+        );
+        assert_eq!(render(&diff_lines("a\nb", "a\nx")), "  a\n- b\n+ x\n");
+    }
+
+    #[test]
+    fn empty_outputs_are_all_added_or_removed() {
+        assert_eq!(diff_lines("", ""), []);
+        assert_eq!(diff_lines("", "a\nb"), [Insert("a"), Insert("b")]);
+        assert_eq!(diff_lines("a\nb", ""), [Delete("a"), Delete("b")]);
+    }
+
+    #[test]
+    fn large_outputs_are_wholly_replaced() {
+        let lines = "same\n".repeat(2001);
+        let changes = diff_lines(&lines, &lines);
+        assert_eq!(changes.len(), 4002);
+        assert!(changes[..2001]
+            .iter()
+            .all(|change| *change == Delete("same")));
+        assert!(changes[2001..]
+            .iter()
+            .all(|change| *change == Insert("same")));
+        // Just under the limit, the diff is exact
+        let lines = "same\n".repeat(2000);
+        assert!(diff_lines(&lines, &lines)
+            .iter()
+            .all(|change| *change == Equal("same")));
+    }
+}
@@ -0,0 +1,580 @@
+//! A small expression language for the `assert` comparison.
+//!
+//! An expression is evaluated against a program's output and must produce
+//! `true` or `false`, for example `lines() == 5 && last_line() matches /DONE/`.
+//!
+//! ```text
+//! or      := and ("||" and)*
+//! and     := not ("&&" not)*
+//! not     := "!" not | compare
+//! compare := primary (("==" | "!=" | "<" | "<=" | ">" | ">=" | "matches") primary)?
+//! primary := number | string | regex | "true" | "false" | call | "(" or ")"
+//! call    := identifier "(" (or ("," or)*)? ")"
+//! ```
+//!
+//! Strings are double-quoted and regexes are delimited by slashes. Both
+//! support backslash escapes of their delimiter. The functions are:
+//!
+//! - `output()`: the whole output
+//! - `lines()`: the number of lines in the output
+//! - `line(n)`: the nth line of the output, counting from 1
+//! - `last_line()`: the last line of the output
+//! - `contains(text)` or `contains(haystack, needle)`: whether the output or
+//!   `haystack` contains the text
+//! - `len(text)`: the number of characters in the text
+//! - `number(text)`: the text parsed as a number, ignoring surrounding whitespace
+
+use regex::Regex;
+use std::fmt;
+use thiserror::Error;
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{message} at character {position} of the assertion")]
+pub struct ParseError {
+    message: String,
+    position: usize,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expression {
+    root: Node,
+}
+
+impl Expression {
+    pub fn parse(source: &str) -> Result<Self, ParseError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            tokens,
+            next: 0,
+            end: source.chars().count(),
+        };
+        let root = parser.or()?;
+        match parser.peek() {
+            None => Ok(Self { root }),
+            Some((_, position)) => Err(ParseError {
+                message: "Expected the end of the assertion".to_string(),
+                position,
+            }),
+        }
+    }
+
+    /// Evaluates the assertion, failing with an explanation if it can't be
+    /// decided for this output
+    pub fn evaluate(&self, output: &str) -> Result<bool, String> {
+        match self.root.evaluate(output)? {
+            Value::Bool(b) => Ok(b),
+            other => Err(format!(
+                "The assertion produced {} instead of true or false",
+                other
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Value {
+    Bool(bool),
+    Number(f64),
+    Str(String),
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Str(s) => write!(f, "{:?}", s),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    Matches,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Function {
+    Output,
+    Lines,
+    Line,
+    LastLine,
+    Contains,
+    Len,
+    Number,
+}
+
+impl Function {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "output" => Some(Function::Output),
+            "lines" => Some(Function::Lines),
+            "line" => Some(Function::Line),
+            "last_line" => Some(Function::LastLine),
+            "contains" => Some(Function::Contains),
+            "len" => Some(Function::Len),
+            "number" => Some(Function::Number),
+            _ => None,
+        }
+    }
+
+    fn accepts(&self, argument_count: usize) -> bool {
+        match self {
+            Function::Output | Function::Lines | Function::LastLine => argument_count == 0,
+            Function::Line | Function::Len | Function::Number => argument_count == 1,
+            Function::Contains => argument_count == 1 || argument_count == 2,
+        }
+    }
+
+    fn call(&self, output: &str, arguments: &[Value]) -> Result<Value, String> {
+        Ok(match (self, arguments) {
+            (Function::Output, []) => Value::Str(output.to_string()),
+            (Function::Lines, []) => Value::Number(output.lines().count() as f64),
+            (Function::LastLine, []) => {
+                Value::Str(output.lines().last().unwrap_or_default().to_string())
+            }
+            (Function::Line, [Value::Number(n)]) => {
+                let line_count = output.lines().count();
+                if n.fract() != 0.0 || *n < 1.0 || *n as usize > line_count {
+                    return Err(format!(
+                        "There is no line {}, the output has {} lines",
+                        n, line_count
+                    ));
+                }
+                Value::Str(output.lines().nth(*n as usize - 1).unwrap().to_string())
+            }
+            (Function::Contains, [Value::Str(needle)]) => Value::Bool(output.contains(needle)),
+            (Function::Contains, [Value::Str(haystack), Value::Str(needle)]) => {
+                Value::Bool(haystack.contains(needle))
+            }
+            (Function::Len, [Value::Str(s)]) => Value::Number(s.chars().count() as f64),
+            (Function::Number, [Value::Str(s)]) => match s.trim().parse() {
+                Ok(n) => Value::Number(n),
+                Err(_) => return Err(format!("{:?} is not a number", s)),
+            },
+            (function, arguments) => {
+                let arguments: Vec<_> = arguments.iter().map(|a| a.to_string()).collect();
+                return Err(format!(
+                    "{:?} can't be called with ({})",
+                    function,
+                    arguments.join(", ")
+                ));
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(Value),
+    Call(Function, Vec<Node>),
+    Not(Box<Node>),
+    And(Box<Node>, Box<Node>),
+    Or(Box<Node>, Box<Node>),
+    Compare(Box<Node>, Operator, Box<Node>),
+}
+
+impl Node {
+    fn evaluate(&self, output: &str) -> Result<Value, String> {
+        match self {
+            Node::Literal(value) => Ok(value.clone()),
+            Node::Call(function, arguments) => {
+                let arguments = arguments
+                    .iter()
+                    .map(|argument| argument.evaluate(output))
+                    .collect::<Result<Vec<_>, _>>()?;
+                function.call(output, &arguments)
+            }
+            Node::Not(operand) => Ok(Value::Bool(!operand.evaluate_bool(output)?)),
+            Node::And(lhs, rhs) => Ok(Value::Bool(
+                lhs.evaluate_bool(output)? && rhs.evaluate_bool(output)?,
+            )),
+            Node::Or(lhs, rhs) => Ok(Value::Bool(
+                lhs.evaluate_bool(output)? || rhs.evaluate_bool(output)?,
+            )),
+            Node::Compare(lhs, operator, rhs) => {
+                let lhs = lhs.evaluate(output)?;
+                let rhs = rhs.evaluate(output)?;
+                compare(&lhs, *operator, &rhs).map(Value::Bool)
+            }
+        }
+    }
+
+    fn evaluate_bool(&self, output: &str) -> Result<bool, String> {
+        match self.evaluate(output)? {
+            Value::Bool(b) => Ok(b),
+            other => Err(format!("Expected true or false, but found {}", other)),
+        }
+    }
+}
+
+fn compare(lhs: &Value, operator: Operator, rhs: &Value) -> Result<bool, String> {
+    match (lhs, operator, rhs) {
+        (_, Operator::Eq, _) if same_type(lhs, rhs) => Ok(lhs == rhs),
+        (_, Operator::Ne, _) if same_type(lhs, rhs) => Ok(lhs != rhs),
+        (Value::Number(a), Operator::Lt, Value::Number(b)) => Ok(a < b),
+        (Value::Number(a), Operator::Le, Value::Number(b)) => Ok(a <= b),
+        (Value::Number(a), Operator::Gt, Value::Number(b)) => Ok(a > b),
+        (Value::Number(a), Operator::Ge, Value::Number(b)) => Ok(a >= b),
+        (Value::Str(text), Operator::Matches, Value::Str(pattern)) => Regex::new(pattern)
+            .map(|re| re.is_match(text))
+            .map_err(|error| format!("Invalid regex /{}/: {}", pattern, error)),
+        _ => Err(format!("Can't compare {} {:?} {}", lhs, operator, rhs)),
+    }
+}
+
+fn same_type(lhs: &Value, rhs: &Value) -> bool {
+    std::mem::discriminant(lhs) == std::mem::discriminant(rhs)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Str(String),
+    Regex(String),
+    Identifier(String),
+    LeftParen,
+    RightParen,
+    Comma,
+    And,
+    Or,
+    Not,
+    Operator(Operator),
+}
+
+fn tokenize(source: &str) -> Result<Vec<(Token, usize)>, ParseError> {
+    let chars: Vec<char> = source.chars().collect();
+    let mut tokens = vec![];
+    let mut i = 0;
+    while i < chars.len() {
+        let start = i;
+        let c = chars[i];
+        let next = chars.get(i + 1).copied();
+        let token = match (c, next) {
+            (c, _) if c.is_whitespace() => {
+                i += 1;
+                continue;
+            }
+            ('(', _) => Token::LeftParen,
+            (')', _) => Token::RightParen,
+            (',', _) => Token::Comma,
+            ('&', Some('&')) => Token::And,
+            ('|', Some('|')) => Token::Or,
+            ('=', Some('=')) => Token::Operator(Operator::Eq),
+            ('!', Some('=')) => Token::Operator(Operator::Ne),
+            ('<', Some('=')) => Token::Operator(Operator::Le),
+            ('>', Some('=')) => Token::Operator(Operator::Ge),
+            ('!', _) => Token::Not,
+            ('<', _) => Token::Operator(Operator::Lt),
+            ('>', _) => Token::Operator(Operator::Gt),
+            ('"', _) => Token::Str(delimited(&chars, &mut i, '"')?),
+            ('/', _) => Token::Regex(delimited(&chars, &mut i, '/')?),
+            (c, _) if c.is_ascii_digit() => {
+                while i < chars.len() && (chars[i].is_ascii_digit() || chars[i] == '.') {
+                    i += 1;
+                }
+                let text: String = chars[start..i].iter().collect();
+                let number = text.parse().map_err(|_| ParseError {
+                    message: format!("Invalid number {}", text),
+                    position: start,
+                })?;
+                tokens.push((Token::Number(number), start));
+                continue;
+            }
+            (c, _) if c.is_alphabetic() || c == '_' => {
+                while i < chars.len() && (chars[i].is_alphanumeric() || chars[i] == '_') {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let token = match word.as_str() {
+                    "matches" => Token::Operator(Operator::Matches),
+                    _ => Token::Identifier(word),
+                };
+                tokens.push((token, start));
+                continue;
+            }
+            (c, _) => {
+                return Err(ParseError {
+                    message: format!("Unexpected character '{}'", c),
+                    position: start,
+                })
+            }
+        };
+        i += match token {
+            Token::And | Token::Or => 2,
+            Token::Operator(Operator::Eq)
+            | Token::Operator(Operator::Ne)
+            | Token::Operator(Operator::Le)
+            | Token::Operator(Operator::Ge) => 2,
+            // Delimited tokens have already advanced past their contents
+            Token::Str(_) | Token::Regex(_) => 0,
+            _ => 1,
+        };
+        tokens.push((token, start));
+    }
+    Ok(tokens)
+}
+
+/// Reads a string or regex literal starting at the opening delimiter
+fn delimited(chars: &[char], i: &mut usize, delimiter: char) -> Result<String, ParseError> {
+    let start = *i;
+    let mut text = String::new();
+    *i += 1;
+    while let Some(&c) = chars.get(*i) {
+        *i += 1;
+        match c {
+            c if c == delimiter => return Ok(text),
+            '\\' => match chars.get(*i) {
+                Some(&escaped) => {
+                    *i += 1;
+                    match (delimiter, escaped) {
+                        (_, c) if c == delimiter => text.push(c),
+                        ('"', 'n') => text.push('\n'),
+                        ('"', 't') => text.push('\t'),
+                        ('"', '\\') => text.push('\\'),
+                        // Regexes keep their escapes for the regex engine
+                        (_, c) => {
+                            text.push('\\');
+                            text.push(c);
+                        }
+                    }
+                }
+                None => break,
+            },
+            c => text.push(c),
+        }
+    }
+    Err(ParseError {
+        message: format!(
+            "Unterminated {}",
+            if delimiter == '"' { "string" } else { "regex" }
+        ),
+        position: start,
+    })
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    next: usize,
+    /// Where errors at the end of the input are reported
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<(&Token, usize)> {
+        self.tokens
+            .get(self.next)
+            .map(|(token, position)| (token, *position))
+    }
+
+    fn advance(&mut self) -> Option<(Token, usize)> {
+        let token = self.tokens.get(self.next).cloned();
+        self.next += 1;
+        token
+    }
+
+    fn position(&self) -> usize {
+        self.peek().map_or(self.end, |(_, position)| position)
+    }
+
+    fn expect(&mut self, expected: Token, description: &str) -> Result<(), ParseError> {
+        match self.peek() {
+            Some((token, _)) if *token == expected => {
+                self.next += 1;
+                Ok(())
+            }
+            _ => Err(ParseError {
+                message: format!("Expected {}", description),
+                position: self.position(),
+            }),
+        }
+    }
+
+    fn or(&mut self) -> Result<Node, ParseError> {
+        let mut lhs = self.and()?;
+        while let Some((Token::Or, _)) = self.peek() {
+            self.next += 1;
+            lhs = Node::Or(Box::new(lhs), Box::new(self.and()?));
+        }
+        Ok(lhs)
+    }
+
+    fn and(&mut self) -> Result<Node, ParseError> {
+        let mut lhs = self.not()?;
+        while let Some((Token::And, _)) = self.peek() {
+            self.next += 1;
+            lhs = Node::And(Box::new(lhs), Box::new(self.not()?));
+        }
+        Ok(lhs)
+    }
+
+    fn not(&mut self) -> Result<Node, ParseError> {
+        if let Some((Token::Not, _)) = self.peek() {
+            self.next += 1;
+            return Ok(Node::Not(Box::new(self.not()?)));
+        }
+        self.compare()
+    }
+
+    fn compare(&mut self) -> Result<Node, ParseError> {
+        let lhs = self.primary()?;
+        if let Some((&Token::Operator(operator), _)) = self.peek() {
+            self.next += 1;
+            let rhs = self.primary()?;
+            return Ok(Node::Compare(Box::new(lhs), operator, Box::new(rhs)));
+        }
+        Ok(lhs)
+    }
+
+    fn primary(&mut self) -> Result<Node, ParseError> {
+        let position = self.position();
+        match self.advance() {
+            Some((Token::Number(n), _)) => Ok(Node::Literal(Value::Number(n))),
+            Some((Token::Str(s), _)) | Some((Token::Regex(s), _)) => {
+                Ok(Node::Literal(Value::Str(s)))
+            }
+            Some((Token::LeftParen, _)) => {
+                let inner = self.or()?;
+                self.expect(Token::RightParen, "')'")?;
+                Ok(inner)
+            }
+            Some((Token::Identifier(name), _)) if name == "true" => {
+                Ok(Node::Literal(Value::Bool(true)))
+            }
+            Some((Token::Identifier(name), _)) if name == "false" => {
+                Ok(Node::Literal(Value::Bool(false)))
+            }
+            Some((Token::Identifier(name), _)) => {
+                let function = Function::from_name(&name).ok_or_else(|| ParseError {
+                    message: format!("Unknown function {}", name),
+                    position,
+                })?;
+                self.expect(Token::LeftParen, "'(' after the function name")?;
+                let mut arguments = vec![];
+                if let Some((Token::RightParen, _)) = self.peek() {
+                    self.next += 1;
+                } else {
+                    loop {
+                        arguments.push(self.or()?);
+                        match self.advance() {
+                            Some((Token::Comma, _)) => {}
+                            Some((Token::RightParen, _)) => break,
+                            _ => {
+                                self.next -= 1;
+                                return Err(ParseError {
+                                    message: "Expected ',' or ')'".to_string(),
+                                    position: self.position(),
+                                });
+                            }
+                        }
+                    }
+                }
+                if !function.accepts(arguments.len()) {
+                    return Err(ParseError {
+                        message: format!("{} can't take {} arguments", name, arguments.len()),
+                        position,
+                    });
+                }
+                Ok(Node::Call(function, arguments))
+            }
+            _ => Err(ParseError {
+                message: "Expected a value".to_string(),
+                position,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OUTPUT: &str = "Round 1\nRound 2\nScore: 42\nDONE\n";
+
+    fn evaluate(source: &str) -> Result<bool, String> {
+        Expression::parse(source)
+            .map_err(|error| error.to_string())?
+            .evaluate(OUTPUT)
+    }
+
+    #[test]
+    fn counts_lines() {
+        assert_eq!(evaluate("lines() == 4"), Ok(true));
+        assert_eq!(evaluate("lines() > 4"), Ok(false));
+    }
+
+    #[test]
+    fn matches_regex_literals() {
+        assert_eq!(evaluate("last_line() matches /DONE/"), Ok(true));
+        assert_eq!(evaluate("output() matches /^Round \\d$/"), Ok(false));
+        assert_eq!(evaluate("line(3) matches /^Score: \\d+$/"), Ok(true));
+        assert_eq!(evaluate("\"a/b\" matches /a\\/b/"), Ok(true));
+    }
+
+    #[test]
+    fn combines_with_precedence() {
+        assert_eq!(
+            evaluate("lines() == 4 && last_line() matches /DONE/"),
+            Ok(true)
+        );
+        assert_eq!(evaluate("false && true || true"), Ok(true));
+        assert_eq!(evaluate("false && (true || true)"), Ok(false));
+        assert_eq!(evaluate("!contains(\"Round 3\")"), Ok(true));
+        assert_eq!(evaluate("!!true"), Ok(true));
+    }
+
+    #[test]
+    fn compares_numbers() {
+        assert_eq!(evaluate("number(\" 42 \") >= 42"), Ok(true));
+        assert_eq!(evaluate("len(line(1)) < 7.5"), Ok(true));
+        assert_eq!(evaluate("len(line(1)) != 7"), Ok(false));
+    }
+
+    #[test]
+    fn checks_containment() {
+        assert_eq!(evaluate("contains(\"Score\")"), Ok(true));
+        assert_eq!(evaluate("contains(line(2), \"1\")"), Ok(false));
+        assert_eq!(evaluate("contains(\"tab\\there\")"), Ok(false));
+    }
+
+    #[test]
+    fn short_circuits() {
+        assert_eq!(evaluate("false && line(100) == \"\""), Ok(false));
+        assert_eq!(evaluate("true || line(100) == \"\""), Ok(true));
+    }
+
+    #[test]
+    fn reports_evaluation_errors() {
+        assert!(evaluate("line(5) == \"\"")
+            .unwrap_err()
+            .contains("no line 5"));
+        assert!(evaluate("number(line(1)) == 1")
+            .unwrap_err()
+            .contains("not a number"));
+        assert!(evaluate("lines() == \"4\"")
+            .unwrap_err()
+            .contains("Can't compare"));
+        assert!(evaluate("lines()").unwrap_err().contains("instead of true"));
+        assert!(evaluate("lines() && true")
+            .unwrap_err()
+            .contains("Expected true or false"));
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let position = |source| Expression::parse(source).unwrap_err().position;
+        assert_eq!(position("lines( == 4"), 7);
+        assert_eq!(position("linez() == 4"), 0);
+        assert_eq!(position("lines(1)"), 0);
+        assert_eq!(position("\"unterminated"), 0);
+        assert_eq!(position("lines() == 4 4"), 13);
+        assert_eq!(position("lines() ="), 8);
+        assert_eq!(position("lines() =="), 10);
+    }
+}
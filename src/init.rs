@@ -0,0 +1,187 @@
+//! Writing a starter config for `autograder init`.
+//!
+//! The starter config has an example test for each kind of comparison, all
+//! running the same setup and run commands, so that an instructor can keep the
+//! ones that suit the assignment and fill in real inputs and outputs. The
+//! commands are guessed from the build files in the directory, such as a
+//! Makefile or Cargo.toml, and can be given with flags or, from a terminal,
+//! typed at a prompt.
+
+use serde_json::{json, Value as Json};
+use std::io::{self, BufRead, IsTerminal, Write};
+use std::path::Path;
+
+/// The options of `autograder init`
+#[derive(clap::Args, Debug, Clone, PartialEq, Eq)]
+pub struct InitOptions {
+    /// The command that builds the submission. Guessed from the project when not given.
+    #[clap(long)]
+    pub setup: Option<String>,
+    /// The command that runs the submission. Guessed from the project when not given.
+    #[clap(long)]
+    pub run: Option<String>,
+    /// Uses the guessed commands without asking
+    #[clap(short, long)]
+    pub yes: bool,
+    /// Replaces the config if it already exists
+    #[clap(long)]
+    pub force: bool,
+}
+
+/// The kinds of project that `init` knows the commands for
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Project {
+    Make,
+    Cargo,
+    Npm,
+    Unknown,
+}
+
+impl Project {
+    /// Recognizes the project from the build file in the directory
+    pub fn detect(dir: &Path) -> Self {
+        if dir.join("Cargo.toml").is_file() {
+            Project::Cargo
+        } else if dir.join("package.json").is_file() {
+            Project::Npm
+        } else if ["Makefile", "makefile", "GNUmakefile"]
+            .iter()
+            .any(|name| dir.join(name).is_file())
+        {
+            Project::Make
+        } else {
+            Project::Unknown
+        }
+    }
+
+    /// The usual setup and run commands for the project
+    pub fn commands(&self) -> (&'static str, &'static str) {
+        match self {
+            Project::Make => ("make", "./main"),
+            Project::Cargo => (
+                "cargo build --release --quiet",
+                "cargo run --release --quiet",
+            ),
+            Project::Npm => ("npm ci", "npm start --silent"),
+            Project::Unknown => ("", "./main"),
+        }
+    }
+}
+
+/// Settles the setup and run commands from the flags, the project, and, when
+/// the user is at a terminal, their answers to prompts
+pub fn commands(options: &InitOptions, dir: &Path) -> io::Result<(String, String)> {
+    let (setup, run) = Project::detect(dir).commands();
+    let interactive = !options.yes && io::stdin().is_terminal() && io::stdout().is_terminal();
+    let settle = |given: &Option<String>, question: &str, guess: &str| match given {
+        Some(given) => Ok(given.clone()),
+        None if interactive => ask(question, guess),
+        None => Ok(guess.to_string()),
+    };
+    let setup = settle(&options.setup, "Setup command", setup)?;
+    let run = settle(&options.run, "Run command", run)?;
+    Ok((setup, run))
+}
+
+/// Asks a question on stdout, taking an empty answer as the default
+fn ask(question: &str, default: &str) -> io::Result<String> {
+    print!("{} [{}]: ", question, default);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().lock().read_line(&mut answer)?;
+    let answer = answer.trim();
+    Ok(if answer.is_empty() { default } else { answer }.to_string())
+}
+
+/// A config with an example test for each kind of comparison
+pub fn starter_config(setup: &str, run: &str) -> Json {
+    let test = |name: &str, fields: Json| {
+        let mut test = json!({
+            "name": name,
+            "setup": setup,
+            "run": run,
+            "timeout": "10s",
+            "points": 1,
+        });
+        if let (Some(test), Json::Object(fields)) = (test.as_object_mut(), fields) {
+            if setup.is_empty() {
+                test.remove("setup");
+            }
+            test.extend(fields);
+        }
+        test
+    };
+    json!({
+        "tests": [
+            test("Exits successfully", json!({})),
+            test("Prints the greeting somewhere", json!({
+                "input": "World\n",
+                "output": "Hello, World",
+                "comparison": "included",
+            })),
+            test("Prints exactly the greeting", json!({
+                "input": "World\n",
+                "output": "Hello, World!\n",
+                "comparison": "exact",
+            })),
+            test("Prints the greeting, ignoring trailing whitespace", json!({
+                "input": "World\n",
+                "output": "Hello, World!",
+                "comparison": "trimmedLines",
+            })),
+            test("Prints the greeting in any case", json!({
+                "input": "World\n",
+                "output": "hello, world!\n",
+                "comparison": "caseInsensitive",
+            })),
+            test("Prints a greeting that matches a pattern", json!({
+                "input": "World\n",
+                "output": "^Hello, \\w+!$",
+                "comparison": "regex",
+            })),
+            test("Prints the sum to within 0.01", json!({
+                "input": "0.1 0.2\n",
+                "output": "0.3\n",
+                "comparison": "numeric",
+                "tolerance": 0.01,
+            })),
+            test("Prints valid JSON", json!({
+                "input": "World\n",
+                "comparison": "validJson",
+            })),
+        ]
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn projects_are_recognized_by_their_build_files() {
+        let dir = env::temp_dir().join(format!("autograder-init-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        assert_eq!(Project::detect(&dir), Project::Unknown);
+        fs::write(dir.join("Makefile"), "all:\n").unwrap();
+        assert_eq!(Project::detect(&dir), Project::Make);
+        fs::write(dir.join("Cargo.toml"), "[package]\n").unwrap();
+        assert_eq!(Project::detect(&dir), Project::Cargo);
+
+        let options = InitOptions {
+            setup: None,
+            run: Some("./target/release/hello".to_string()),
+            yes: true,
+            force: false,
+        };
+        assert_eq!(
+            commands(&options, &dir).unwrap(),
+            (
+                "cargo build --release --quiet".to_string(),
+                "./target/release/hello".to_string()
+            )
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
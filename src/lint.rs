@@ -0,0 +1,213 @@
+//! Heuristics for spotting shell constructs that make tests nondeterministic.
+//!
+//! The scan follows bash quoting closely enough to ignore anything inside
+//! single quotes and globs inside double quotes, but it is not a shell parser.
+//! It flags:
+//!
+//! - unquoted globs (`*`, `?`, and `[...]`), whose expansion depends on the
+//!   contents of the working directory
+//! - command substitution (`$(...)` and backticks), whose output is not
+//!   controlled by the test
+//! - `$RANDOM`, `$SRANDOM`, and `$$`, which differ on every run
+//!
+//! It can also pick out the program a command starts with, for checking that
+//! the program is installed.
+
+use std::fmt;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    /// The character at which the construct starts, counting from 1
+    pub column: usize,
+    pub message: &'static str,
+}
+
+impl fmt::Display for Finding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at column {}", self.message, self.column)
+    }
+}
+
+/// Finds the nondeterminism-prone constructs in a shell command
+pub fn lint_command(command: &str) -> Vec<Finding> {
+    let chars: Vec<char> = command.chars().collect();
+    let mut findings = vec![];
+    let mut in_double_quotes = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let finding = |message| Finding {
+            column: i + 1,
+            message,
+        };
+        match chars[i] {
+            '\\' => i += 1,
+            '\'' if !in_double_quotes => {
+                while i + 1 < chars.len() && chars[i + 1] != '\'' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '"' => in_double_quotes = !in_double_quotes,
+            '`' => {
+                findings.push(finding("command substitution with backticks"));
+                while i + 1 < chars.len() && chars[i + 1] != '`' {
+                    i += 1;
+                }
+                i += 1;
+            }
+            '$' => match chars.get(i + 1) {
+                Some('(') if chars.get(i + 2) == Some(&'(') => {
+                    // Arithmetic expansion is deterministic, so skip past it
+                    let mut depth = 0;
+                    while i < chars.len() {
+                        match chars[i] {
+                            '(' => depth += 1,
+                            ')' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                        i += 1;
+                    }
+                }
+                Some('(') => findings.push(finding("command substitution with $(...)")),
+                Some('$') => {
+                    findings.push(finding("the process ID $$"));
+                    i += 1;
+                }
+                Some(_) => {
+                    let name: String = chars[i + 1..]
+                        .iter()
+                        .take_while(|c| c.is_ascii_alphanumeric() || **c == '_')
+                        .collect();
+                    if name == "RANDOM" || name == "SRANDOM" {
+                        findings.push(finding("the random number variable"));
+                    }
+                    // Skips special parameters such as $? and $*
+                    i += name.len().max(1);
+                }
+                None => {}
+            },
+            '*' | '?' if !in_double_quotes => findings.push(finding("an unquoted glob")),
+            '[' if !in_double_quotes && is_bracket_glob(&chars[i + 1..]) => {
+                findings.push(finding("an unquoted glob"))
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    findings
+}
+
+/// Whether the text after a `[` closes it within the same word, as in `file[0-9]`
+/// but not the `[ -f file ]` test command
+fn is_bracket_glob(rest: &[char]) -> bool {
+    match rest.first() {
+        None | Some(' ') | Some('\t') | Some('[') => false,
+        Some(_) => rest
+            .iter()
+            .take_while(|c| !c.is_whitespace())
+            .any(|c| *c == ']'),
+    }
+}
+
+/// Words that run the rest of the command rather than being the program themselves
+const PREFIXES: &[&str] = &["command", "env", "exec", "nohup", "time"];
+
+/// Programs built into bash, which don't need to be on the PATH
+const BUILTINS: &[&str] = &[
+    "!", ".", ":", "[", "[[", "{", "(", "alias", "case", "cd", "echo", "eval", "exit", "export",
+    "false", "for", "function", "if", "printf", "pwd", "read", "return", "set", "shift", "source",
+    "test", "trap", "true", "ulimit", "umask", "unset", "until", "wait", "while",
+];
+
+/// Finds the program a command runs first, skipping variable assignments and
+/// prefixes such as `env VAR=value`. Returns `None` for builtins and for programs
+/// that can't be known before running, such as `$CC` or `./built-by-setup`.
+pub fn leading_command(command: &str) -> Option<&str> {
+    for word in command.split_whitespace() {
+        let is_assignment = word
+            .split_once('=')
+            .is_some_and(|(name, _)| !name.is_empty() && !name.contains('/'));
+        if is_assignment || PREFIXES.contains(&word) {
+            continue;
+        }
+        // Options to a prefix, as in `env -i`
+        if word.starts_with('-') {
+            continue;
+        }
+        let word = word.trim_end_matches([';', '&', '|']);
+        if BUILTINS.contains(&word)
+            || word.is_empty()
+            || word.contains(['$', '`', '\'', '"', '(', '<', '>'])
+            || (word.contains('/') && !word.starts_with('/'))
+        {
+            return None;
+        }
+        return Some(word);
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(command: &str) -> Vec<(usize, &'static str)> {
+        lint_command(command)
+            .into_iter()
+            .map(|finding| (finding.column, finding.message))
+            .collect()
+    }
+
+    #[test]
+    fn quoted_globs_are_ignored() {
+        assert_eq!(
+            messages(r#"ls '*.c' "*.h" *.o"#),
+            [(16, "an unquoted glob")]
+        );
+        assert_eq!(messages(r#"echo "it's" ?"#), [(13, "an unquoted glob")]);
+        assert!(messages(r"echo \*").is_empty());
+    }
+
+    #[test]
+    fn arithmetic_is_not_command_substitution() {
+        assert!(messages("echo $((1 + (2 * 3)))").is_empty());
+        assert_eq!(
+            messages("echo $(date) `date`"),
+            [
+                (6, "command substitution with $(...)"),
+                (14, "command substitution with backticks")
+            ]
+        );
+    }
+
+    #[test]
+    fn test_brackets_are_not_globs() {
+        assert!(messages("[ -f x ] && cat x").is_empty());
+        assert!(messages("[[ -n $x ]]").is_empty());
+        assert_eq!(messages("cat file[0-9]"), [(9, "an unquoted glob")]);
+    }
+
+    #[test]
+    fn process_ids_and_random_numbers_are_flagged() {
+        assert_eq!(
+            messages("echo $$ $RANDOM $RANDOMIZED $?"),
+            [(6, "the process ID $$"), (9, "the random number variable")]
+        );
+        assert!(messages("echo '$$ $RANDOM'").is_empty());
+    }
+
+    #[test]
+    fn leading_command_skips_prefixes_and_assignments() {
+        assert_eq!(leading_command("env -i FOO=1 prog --flag"), Some("prog"));
+        assert_eq!(leading_command("CC=gcc make; ./a.out"), Some("make"));
+        assert_eq!(leading_command("time python3 main.py"), Some("python3"));
+        assert_eq!(leading_command("echo hi"), None);
+        assert_eq!(leading_command("./a.out"), None);
+        assert_eq!(leading_command("$CC main.c"), None);
+    }
+}
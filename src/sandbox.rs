@@ -0,0 +1,183 @@
+//! Running the tests inside a container for `--sandbox`.
+//!
+//! One container is started from the image for the whole run. The current
+//! directory is mounted read-only at `/submission` and copied to `/work`, where
+//! the commands run, so setup can build into the copy without touching the
+//! original. The container has no network access, and it is removed with
+//! everything still running in it when grading finishes.
+//!
+//! Each command the grader would have run on the host instead runs through
+//! `docker exec` or `podman exec`, with the same environment variables and with
+//! its working directory moved into `/work`. Killing a command on a timeout only
+//! stops the `exec` client, so a runaway program keeps running inside the
+//! container until the container is removed.
+
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+/// Where the current directory is mounted in the container
+const SUBMISSION_DIR: &str = "/submission";
+/// Where the writable copy of the current directory is made
+const WORK_DIR: &str = "/work";
+
+/// A container that commands can run in, which is removed when dropped
+#[derive(Debug)]
+pub struct Container {
+    /// The container engine's program, `docker` or `podman`
+    engine: String,
+    id: String,
+    /// The host directory mounted in the container
+    submission: PathBuf,
+}
+
+impl Container {
+    /// Starts a container of the image with the directory mounted and copied
+    pub fn start(engine: &str, image: &str, submission: &Path) -> Result<Self, String> {
+        let submission = submission.canonicalize().map_err(|error| {
+            format!(
+                "Could not find the directory {}: {}",
+                submission.display(),
+                error
+            )
+        })?;
+        let volume = format!("{}:{}:ro", submission.display(), SUBMISSION_DIR);
+        let output = Command::new(engine)
+            .args(["run", "--detach", "--rm", "--network", "none", "--volume"])
+            .arg(volume)
+            .args([image, "sleep", "infinity"])
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|error| format!("Could not run {}: {}", engine, error))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Could not start a container of {} with {}\n{}",
+                image,
+                engine,
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        let container = Self {
+            engine: engine.to_string(),
+            id: String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            submission,
+        };
+        let copy = format!("mkdir -p {0} && cp -a {1}/. {0}", WORK_DIR, SUBMISSION_DIR);
+        let output = Command::new(engine)
+            .args(["exec", &container.id, "sh", "-c", &copy])
+            .stdin(Stdio::null())
+            .output()
+            .map_err(|error| format!("Could not run {}: {}", engine, error))?;
+        if !output.status.success() {
+            return Err(format!(
+                "Could not copy the submission into the container\n{}",
+                String::from_utf8_lossy(&output.stderr)
+            ));
+        }
+        Ok(container)
+    }
+
+    /// Turns a command meant for the host into one that runs in the container.
+    /// Only the program, arguments, environment variables, and working directory
+    /// carry over, so this comes before setting up the command's stdio.
+    pub fn wrap(&self, command: &Command) -> Result<Command, String> {
+        let dir = match command.get_current_dir() {
+            Some(dir) => self.container_path(dir)?,
+            None => WORK_DIR.to_string(),
+        };
+        let mut wrapped = Command::new(&self.engine);
+        wrapped.args(["exec", "--interactive", "--workdir", &dir]);
+        for (key, value) in command.get_envs() {
+            if let Some(value) = value {
+                wrapped.arg("--env").arg(format!(
+                    "{}={}",
+                    key.to_string_lossy(),
+                    value.to_string_lossy()
+                ));
+            }
+        }
+        wrapped
+            .arg(&self.id)
+            .arg(command.get_program())
+            .args(command.get_args());
+        Ok(wrapped)
+    }
+
+    /// Where a directory on the host is found in the container's copy
+    fn container_path(&self, dir: &Path) -> Result<String, String> {
+        let outside = || {
+            format!(
+                "The working directory {} is outside {}, which is all the container can see",
+                dir.display(),
+                self.submission.display()
+            )
+        };
+        let dir = dir.canonicalize().map_err(|_| outside())?;
+        let relative = dir.strip_prefix(&self.submission).map_err(|_| outside())?;
+        let mut path = WORK_DIR.to_string();
+        for component in relative.components() {
+            path.push('/');
+            path += &component.as_os_str().to_string_lossy();
+        }
+        Ok(path)
+    }
+}
+
+impl Drop for Container {
+    fn drop(&mut self) {
+        let _ = Command::new(&self.engine)
+            .args(["rm", "--force", &self.id])
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn commands_move_into_the_copy() {
+        let dir = env::temp_dir().join(format!("autograder-sandbox-{}", std::process::id()));
+        fs::create_dir_all(dir.join("part1")).unwrap();
+        let submission = dir.canonicalize().unwrap();
+        // Removing the container runs the engine, which does nothing here
+        let container = Container {
+            engine: "true".to_string(),
+            id: "abc123".to_string(),
+            submission: submission.clone(),
+        };
+        let mut command = Command::new("bash");
+        command
+            .args(["-c", "make test"])
+            .env("LEVEL", "2")
+            .current_dir(submission.join("part1"));
+        let wrapped = container.wrap(&command).unwrap();
+        let args: Vec<_> = wrapped
+            .get_args()
+            .map(|arg| arg.to_str().unwrap())
+            .collect();
+        assert_eq!(
+            args,
+            [
+                "exec",
+                "--interactive",
+                "--workdir",
+                "/work/part1",
+                "--env",
+                "LEVEL=2",
+                "abc123",
+                "bash",
+                "-c",
+                "make test"
+            ]
+        );
+
+        command.current_dir(env::temp_dir());
+        assert!(container.wrap(&command).is_err());
+        fs::remove_dir_all(&submission).unwrap();
+    }
+}
@@ -0,0 +1,432 @@
+//! Browsing the results in the terminal for `autograder tui`.
+//!
+//! The browser takes over the terminal's alternate screen, listing the tests on
+//! the left and showing the details of the selected one on the right. It draws
+//! with ANSI escape codes and reads keys in raw mode set through termios, so it
+//! needs no terminal library but only works on Unix. Results arrive on a channel
+//! as the tests finish, and reruns are asked for on another, so the screen stays
+//! responsive while tests run.
+
+use colored::{ColoredString, Colorize};
+use regex::Regex;
+use std::io::{self, Write};
+use std::sync::mpsc::{Receiver, Sender};
+
+/// How long to wait for a key before looking for new results again
+const TICK_MS: i32 = 100;
+/// The keys, shown at the bottom of the screen
+const HELP: &str = "↑↓ select  PgUp PgDn scroll  r rerun  a rerun all  q quit";
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Status {
+    Pending,
+    Running,
+    Passed,
+    Failed,
+    Error,
+    Skipped,
+}
+
+impl Status {
+    /// A one-column mark for the list of tests
+    fn marker(self) -> ColoredString {
+        match self {
+            Status::Pending => "·".dimmed(),
+            Status::Running => "…".yellow(),
+            Status::Passed => "✓".green(),
+            Status::Failed => "✗".red(),
+            Status::Error => "!".red(),
+            Status::Skipped => "»".dimmed(),
+        }
+    }
+}
+
+/// A titled part of a test's details, such as its stdout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Section {
+    pub title: String,
+    pub text: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Update {
+    /// The tests at these positions are running again
+    Running(Vec<usize>),
+    Finished {
+        index: usize,
+        status: Status,
+        details: Vec<Section>,
+    },
+    /// A line about the whole run, such as the points so far
+    Summary(String),
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Request {
+    Rerun(usize),
+    RerunAll,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Key {
+    Up,
+    Down,
+    PageUp,
+    PageDown,
+    Home,
+    End,
+    Rerun,
+    RerunAll,
+    Quit,
+}
+
+struct Browser {
+    names: Vec<String>,
+    statuses: Vec<Status>,
+    details: Vec<Vec<Section>>,
+    summary: String,
+    selected: usize,
+    /// How many lines of the details are scrolled past
+    scroll: usize,
+}
+
+/// Shows the tests until the user quits, applying the updates as they arrive
+/// and sending the reruns the user asks for
+pub fn browse(
+    names: Vec<String>,
+    updates: Receiver<Update>,
+    requests: Sender<Request>,
+) -> io::Result<()> {
+    let _terminal = RawTerminal::enter()?;
+    let mut browser = Browser {
+        statuses: vec![Status::Pending; names.len()],
+        details: vec![vec![]; names.len()],
+        names,
+        summary: String::new(),
+        selected: 0,
+        scroll: 0,
+    };
+    let mut stdout = io::stdout();
+    let mut drawn_size = None;
+    let mut changed = true;
+    loop {
+        for update in updates.try_iter() {
+            browser.apply(update);
+            changed = true;
+        }
+        let size = terminal_size();
+        if changed || drawn_size != Some(size) {
+            stdout.write_all(browser.render(size.0, size.1).as_bytes())?;
+            stdout.flush()?;
+            drawn_size = Some(size);
+            changed = false;
+        }
+        for key in read_keys()? {
+            changed = true;
+            let page = size.0.saturating_sub(3).max(1);
+            match key {
+                Key::Quit => return Ok(()),
+                Key::Up => browser.select(browser.selected.saturating_sub(1)),
+                Key::Down => browser.select(browser.selected + 1),
+                Key::Home => browser.select(0),
+                Key::End => browser.select(usize::MAX),
+                Key::PageUp => browser.scroll = browser.scroll.saturating_sub(page),
+                Key::PageDown => browser.scroll += page,
+                // The grader has stopped when nobody is listening, so there is nothing to do
+                Key::Rerun => {
+                    let _ = requests.send(Request::Rerun(browser.selected));
+                }
+                Key::RerunAll => {
+                    let _ = requests.send(Request::RerunAll);
+                }
+            }
+        }
+    }
+}
+
+impl Browser {
+    fn apply(&mut self, update: Update) {
+        match update {
+            Update::Running(indices) => {
+                for i in indices {
+                    self.statuses[i] = Status::Running;
+                }
+            }
+            Update::Finished {
+                index,
+                status,
+                details,
+            } => {
+                self.statuses[index] = status;
+                self.details[index] = details;
+            }
+            Update::Summary(summary) => self.summary = summary,
+        }
+    }
+
+    fn select(&mut self, selected: usize) {
+        let selected = selected.min(self.names.len().saturating_sub(1));
+        if selected != self.selected {
+            self.selected = selected;
+            self.scroll = 0;
+        }
+    }
+
+    /// Draws the whole screen, which has this many rows and columns
+    fn render(&mut self, rows: usize, cols: usize) -> String {
+        // Moves to the top left
+        let mut screen = String::from("\x1b[H");
+        if rows < 4 || cols < 24 {
+            screen += "The terminal is too small\x1b[J";
+            return screen;
+        }
+        let body = rows - 2;
+        let list_width = (cols / 3).clamp(16, 40);
+        let detail_width = cols - list_width - 3;
+        let details = self.detail_lines(detail_width);
+        self.scroll = self.scroll.min(details.len().saturating_sub(body));
+        // Keeps the selected test in view
+        let first = self.selected.saturating_sub(body - 1);
+
+        let title = format!(" autograder  {}", self.summary);
+        screen += &format!("{}\r\n", fit(&title, cols).reversed());
+        for row in 0..body {
+            let entry = match self.names.get(first + row) {
+                Some(name) => {
+                    let i = first + row;
+                    let mut parts = [
+                        " ".normal(),
+                        self.statuses[i].marker(),
+                        format!(" {}", fit(name, list_width - 3)).normal(),
+                    ];
+                    // Each part is reversed, since the marker's color ends with a reset
+                    if i == self.selected {
+                        for part in parts.iter_mut() {
+                            *part = part.clone().reversed();
+                        }
+                    }
+                    parts.iter().map(ToString::to_string).collect()
+                }
+                None => " ".repeat(list_width),
+            };
+            let detail = details.get(self.scroll + row).map_or("", String::as_str);
+            screen += &format!("{}{} {}\x1b[K\r\n", entry, "│".dimmed(), detail);
+        }
+        screen += &format!("{}\x1b[K", fit(HELP, cols).dimmed());
+        screen
+    }
+
+    /// The selected test's details, wrapped to the width and styled
+    fn detail_lines(&self, width: usize) -> Vec<String> {
+        let sections = match self.details.get(self.selected) {
+            Some(sections) => sections,
+            None => return vec![],
+        };
+        if sections.is_empty() {
+            let waiting = match self.statuses[self.selected] {
+                Status::Running => "Running…",
+                _ => "Waiting to run",
+            };
+            return vec![waiting.dimmed().to_string()];
+        }
+        let mut lines = vec![];
+        for section in sections {
+            lines.push(fit(&section.title, width).bold().to_string());
+            for line in clean(&section.text).lines() {
+                for part in wrap(line, width) {
+                    let styled = match section.title.as_str() {
+                        "Diff" if line.starts_with("- ") => part.red().to_string(),
+                        "Diff" if line.starts_with("+ ") => part.green().to_string(),
+                        _ => part,
+                    };
+                    lines.push(styled);
+                }
+            }
+            lines.push(String::new());
+        }
+        lines
+    }
+}
+
+/// Cuts the text to the width in characters, padding it out with spaces
+fn fit(text: &str, width: usize) -> String {
+    let mut fitted: String = text.chars().take(width).collect();
+    let len = fitted.chars().count();
+    fitted += &" ".repeat(width - len);
+    fitted
+}
+
+/// Splits a line into pieces of at most the width in characters
+fn wrap(line: &str, width: usize) -> Vec<String> {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.is_empty() {
+        return vec![String::new()];
+    }
+    chars
+        .chunks(width.max(1))
+        .map(|chunk| chunk.iter().collect())
+        .collect()
+}
+
+/// Takes out the escape codes and other control characters in what a program
+/// printed, which would move the cursor or change colors on the screen
+fn clean(text: &str) -> String {
+    let escapes = Regex::new(r"\x1b\[[0-9;?]*[ -/]*[@-~]|\x1b.").unwrap();
+    escapes
+        .replace_all(text, "")
+        .replace('\t', "    ")
+        .chars()
+        .filter(|c| *c == '\n' || !c.is_control())
+        .collect()
+}
+
+/// The keys in what was read from the terminal
+fn parse_keys(bytes: &[u8]) -> Vec<Key> {
+    let mut keys = vec![];
+    let mut rest = bytes;
+    while let Some((&byte, after)) = rest.split_first() {
+        rest = after;
+        let key = match byte {
+            b'k' => Key::Up,
+            b'j' => Key::Down,
+            b'g' => Key::Home,
+            b'G' => Key::End,
+            b'u' => Key::PageUp,
+            b'd' | b' ' => Key::PageDown,
+            b'r' => Key::Rerun,
+            b'a' => Key::RerunAll,
+            // Ctrl-C, since raw mode stops it from interrupting
+            b'q' | 3 => Key::Quit,
+            0x1b => {
+                let (key, length) = match rest {
+                    [b'[', b'A', ..] => (Some(Key::Up), 2),
+                    [b'[', b'B', ..] => (Some(Key::Down), 2),
+                    [b'[', b'H', ..] => (Some(Key::Home), 2),
+                    [b'[', b'F', ..] => (Some(Key::End), 2),
+                    [b'[', b'5', b'~', ..] => (Some(Key::PageUp), 3),
+                    [b'[', b'6', b'~', ..] => (Some(Key::PageDown), 3),
+                    _ => (None, 0),
+                };
+                rest = &rest[length..];
+                match key {
+                    Some(key) => key,
+                    None => continue,
+                }
+            }
+            _ => continue,
+        };
+        keys.push(key);
+    }
+    keys
+}
+
+/// Waits a moment for keys, returning the ones that were pressed
+fn read_keys() -> io::Result<Vec<Key>> {
+    let mut stdin = libc::pollfd {
+        fd: libc::STDIN_FILENO,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    match unsafe { libc::poll(&mut stdin, 1, TICK_MS) } {
+        0 => return Ok(vec![]),
+        ready if ready < 0 => {
+            let error = io::Error::last_os_error();
+            return match error.kind() {
+                // A resize interrupts the wait
+                io::ErrorKind::Interrupted => Ok(vec![]),
+                _ => Err(error),
+            };
+        }
+        _ => {}
+    }
+    // Read directly, since the buffering of io::stdin would hide keys from poll
+    let mut buffer = [0u8; 64];
+    let read = unsafe { libc::read(libc::STDIN_FILENO, buffer.as_mut_ptr().cast(), buffer.len()) };
+    if read < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(parse_keys(&buffer[..read as usize]))
+}
+
+/// The rows and columns of the terminal
+fn terminal_size() -> (usize, usize) {
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let found = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) } == 0;
+    if found && size.ws_row > 0 && size.ws_col > 0 {
+        (size.ws_row.into(), size.ws_col.into())
+    } else {
+        (24, 80)
+    }
+}
+
+/// The terminal in raw mode on the alternate screen, which is put back as it
+/// was when dropped
+struct RawTerminal {
+    original: libc::termios,
+}
+
+impl RawTerminal {
+    fn enter() -> io::Result<Self> {
+        let mut original: libc::termios = unsafe { std::mem::zeroed() };
+        if unsafe { libc::tcgetattr(libc::STDIN_FILENO, &mut original) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let mut raw = original;
+        raw.c_lflag &= !(libc::ICANON | libc::ECHO | libc::ISIG | libc::IEXTEN);
+        raw.c_iflag &= !(libc::IXON | libc::ICRNL);
+        if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &raw) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Switches to the alternate screen and hides the cursor
+        let mut stdout = io::stdout();
+        stdout.write_all(b"\x1b[?1049h\x1b[?25l\x1b[2J")?;
+        stdout.flush()?;
+        Ok(Self { original })
+    }
+}
+
+impl Drop for RawTerminal {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = stdout.write_all(b"\x1b[?25h\x1b[?1049l");
+        let _ = stdout.flush();
+        unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSAFLUSH, &self.original) };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_and_screens_are_read_and_drawn() {
+        assert_eq!(
+            parse_keys(b"j\x1b[A\x1b[6~x\x1bq"),
+            [Key::Down, Key::Up, Key::PageDown, Key::Quit]
+        );
+        assert_eq!(clean("\x1b[31mred\x1b[0m\tdone\r"), "red    done");
+        assert_eq!(wrap("abcde", 2), ["ab", "cd", "e"]);
+
+        colored::control::set_override(false);
+        let mut browser = Browser {
+            names: vec!["first".to_string(), "second".to_string()],
+            statuses: vec![Status::Pending; 2],
+            details: vec![vec![]; 2],
+            summary: String::new(),
+            selected: 0,
+            scroll: 0,
+        };
+        browser.apply(Update::Finished {
+            index: 0,
+            status: Status::Failed,
+            details: vec![Section {
+                title: "Diff".to_string(),
+                text: "- hi\n+ ho\n".to_string(),
+            }],
+        });
+        let screen = browser.render(6, 60);
+        assert!(screen.contains(" ✗ first"));
+        assert!(screen.contains("│ - hi"));
+        assert!(screen.contains(" · second"));
+    }
+}
@@ -0,0 +1,90 @@
+//! Noticing file changes for `--watch`.
+//!
+//! The watcher polls the modification times and sizes of the files under a
+//! directory rather than subscribing to the platform's file events, which keeps
+//! it working the same way on every platform and in containers and network
+//! mounts where events go missing. Hidden files and directories, such as `.git`,
+//! are skipped, since editors and version control touch them constantly.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How long to wait between looking for changes
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The modification time and size of each file that was found
+pub type Snapshot = BTreeMap<PathBuf, (Option<SystemTime>, u64)>;
+
+/// Records every file under each path. A path may also be a single file.
+pub fn snapshot(paths: &[&Path]) -> Snapshot {
+    let mut files = Snapshot::new();
+    for path in paths {
+        record(path, &mut files);
+    }
+    files
+}
+
+fn record(path: &Path, files: &mut Snapshot) {
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        // Files come and go while they are being saved
+        Err(_) => return,
+    };
+    if metadata.is_file() {
+        files.insert(
+            path.to_path_buf(),
+            (metadata.modified().ok(), metadata.len()),
+        );
+        return;
+    }
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries,
+        Err(_) => return,
+    };
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with('.') {
+            record(&entry.path(), files);
+        }
+    }
+}
+
+/// Waits until the files under the paths differ from `before`. Returns false
+/// without waiting further once `stop` returns true.
+pub fn wait_for_change(paths: &[&Path], before: &Snapshot, stop: impl Fn() -> bool) -> bool {
+    loop {
+        thread::sleep(POLL_INTERVAL);
+        if stop() {
+            return false;
+        }
+        if snapshot(paths) != *before {
+            return true;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn snapshot_sees_changes_but_not_hidden_files() {
+        let root = env::temp_dir().join(format!("autograder-watch-{}", std::process::id()));
+        fs::create_dir_all(root.join("src")).unwrap();
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join("src/main.c"), "int main() {}").unwrap();
+        let before = snapshot(&[&root]);
+        assert_eq!(before.len(), 1);
+
+        fs::write(root.join(".git/index"), "changed").unwrap();
+        assert_eq!(snapshot(&[&root]), before);
+
+        fs::write(root.join("src/main.c"), "int main() { return 0; }").unwrap();
+        assert_ne!(snapshot(&[&root]), before);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
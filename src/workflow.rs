@@ -0,0 +1,224 @@
+//! Reading tests from a GitHub Classroom workflow, such as
+//! `.github/workflows/classroom.yml`.
+//!
+//! Classroom now writes each test as a workflow step that uses one of its
+//! grader actions, with the test spelled out in the step's `with` inputs:
+//!
+//! ```yaml
+//! - name: Reverses input
+//!   id: reverses-input
+//!   uses: classroom-resources/autograding-io-grader@v1
+//!   with:
+//!     test-name: Reverses input
+//!     setup-command: make
+//!     command: ./main
+//!     input: abc
+//!     expected-output: cba
+//!     comparison-method: exact
+//!     timeout: 10
+//!     max-score: 1
+//! ```
+//!
+//! Steps using `autograding-io-grader` compare the output with the given
+//! comparison method. Steps using `autograding-command-grader` pass when the
+//! command exits successfully. Every other step, such as the checkout and the
+//! grading reporter, is ignored. As in the workflow, timeouts are minutes.
+
+use serde_json::{Map, Value as Json};
+use serde_yaml::Value as Yaml;
+
+const IO_GRADER: &str = "classroom-resources/autograding-io-grader";
+const COMMAND_GRADER: &str = "classroom-resources/autograding-command-grader";
+
+/// Whether a YAML document is a workflow rather than a config
+pub fn is_workflow(document: &Yaml) -> bool {
+    document.get("jobs").is_some() && document.get("tests").is_none()
+}
+
+/// Converts the grader steps of every job into the tests of a config
+pub fn to_config(document: &Yaml) -> Result<Json, String> {
+    let jobs = document
+        .get("jobs")
+        .and_then(Yaml::as_mapping)
+        .ok_or("The workflow's jobs are not a mapping")?;
+    let mut tests = vec![];
+    for (job_name, job) in jobs {
+        let steps = match job.get("steps") {
+            Some(steps) => steps
+                .as_sequence()
+                .ok_or_else(|| format!("The steps of job {} are not a list", scalar(job_name)))?,
+            None => continue,
+        };
+        for step in steps {
+            if let Some(test) = to_test(step)? {
+                tests.push(test);
+            }
+        }
+    }
+    let mut config = Map::new();
+    config.insert("tests".to_string(), Json::Array(tests));
+    Ok(Json::Object(config))
+}
+
+/// Converts a grader step into a test, or returns `None` for any other step
+fn to_test(step: &Yaml) -> Result<Option<Json>, String> {
+    let uses = step.get("uses").and_then(Yaml::as_str).unwrap_or_default();
+    // Actions are pinned with @, as in autograding-io-grader@v1
+    let action = uses.split('@').next().unwrap_or_default();
+    let is_io = match action {
+        IO_GRADER => true,
+        COMMAND_GRADER => false,
+        _ => return Ok(None),
+    };
+    let input = |key: &str| step.get("with").and_then(|with| with.get(key)).map(scalar);
+    let name = input("test-name")
+        .or_else(|| step.get("name").map(scalar))
+        .ok_or_else(|| format!("A step using {} has no test-name", action))?;
+    let run =
+        input("command").ok_or_else(|| format!("The step for test '{}' has no command", name))?;
+
+    let mut test = Map::new();
+    test.insert("name".to_string(), Json::String(name.clone()));
+    test.insert("run".to_string(), Json::String(run));
+    if let Some(setup) = input("setup-command") {
+        test.insert("setup".to_string(), Json::String(setup));
+    }
+    if let Some(timeout) = input("timeout") {
+        let minutes: u64 = timeout.parse().map_err(|_| {
+            format!(
+                "The step for test '{}' has the timeout '{}', which is not a whole number of minutes",
+                name, timeout
+            )
+        })?;
+        test.insert("timeout".to_string(), Json::from(minutes));
+    }
+    if let Some(max_score) = input("max-score") {
+        let points: u16 = max_score.parse().map_err(|_| {
+            format!(
+                "The step for test '{}' has the max-score '{}', which is not a whole number",
+                name, max_score
+            )
+        })?;
+        test.insert("points".to_string(), Json::from(points));
+    }
+    if is_io {
+        if let Some(stdin) = input("input") {
+            test.insert("input".to_string(), Json::String(stdin));
+        }
+        if let Some(expected) = input("expected-output") {
+            test.insert("output".to_string(), Json::String(expected));
+        }
+        let comparison = match input("comparison-method").as_deref() {
+            None | Some("included") => "included",
+            Some("exact") => "exact",
+            Some("regex") => "regex",
+            Some(other) => {
+                return Err(format!(
+                    "The step for test '{}' has the unknown comparison-method '{}'",
+                    name, other
+                ))
+            }
+        };
+        test.insert("comparison".to_string(), Json::from(comparison));
+    }
+    Ok(Some(Json::Object(test)))
+}
+
+/// Action inputs are strings to GitHub, however they are written in the YAML
+fn scalar(value: &Yaml) -> String {
+    match value {
+        Yaml::String(text) => text.clone(),
+        Yaml::Number(number) => number.to_string(),
+        Yaml::Bool(boolean) => boolean.to_string(),
+        Yaml::Null => String::new(),
+        other => serde_yaml::to_string(other).unwrap_or_default(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKFLOW: &str = "
+name: Autograding Tests
+'on':
+- push
+jobs:
+  run-autograding-tests:
+    runs-on: ubuntu-latest
+    steps:
+    - name: Checkout code
+      uses: actions/checkout@v4
+    - name: Reverses input
+      id: reverses-input
+      uses: classroom-resources/autograding-io-grader@v1
+      with:
+        test-name: Reverses input
+        setup-command: make
+        command: ./main
+        input: abc
+        expected-output: cba
+        comparison-method: exact
+        timeout: 10
+        max-score: 2
+    - name: Builds cleanly
+      id: builds-cleanly
+      uses: classroom-resources/autograding-command-grader@v1
+      with:
+        test-name: Builds cleanly
+        setup-command: ''
+        command: make check
+        timeout: 5
+        max-score: 1
+    - name: Autograding Reporter
+      uses: classroom-resources/autograding-grading-reporter@v1
+      with:
+        runners: reverses-input,builds-cleanly
+";
+
+    #[test]
+    fn grader_steps_become_tests() {
+        let document: Yaml = serde_yaml::from_str(WORKFLOW).unwrap();
+        assert!(is_workflow(&document));
+        assert_eq!(
+            to_config(&document).unwrap(),
+            serde_json::json!({"tests": [
+                {
+                    "name": "Reverses input",
+                    "run": "./main",
+                    "setup": "make",
+                    "timeout": 10,
+                    "points": 2,
+                    "input": "abc",
+                    "output": "cba",
+                    "comparison": "exact"
+                },
+                {
+                    "name": "Builds cleanly",
+                    "run": "make check",
+                    "setup": "",
+                    "timeout": 5,
+                    "points": 1
+                }
+            ]})
+        );
+    }
+
+    #[test]
+    fn unknown_comparison_method_is_an_error() {
+        let document: Yaml = serde_yaml::from_str(
+            "
+jobs:
+  grade:
+    steps:
+    - uses: classroom-resources/autograding-io-grader@v1
+      with:
+        test-name: fuzzy
+        command: ./main
+        comparison-method: fuzzy
+",
+        )
+        .unwrap();
+        assert!(to_config(&document).unwrap_err().contains("fuzzy"));
+    }
+}
@@ -0,0 +1,119 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Writes a config to its own temporary directory
+fn write_config(name: &str, json: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("autograder-cli-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let path = dir.join("autograding.json");
+    fs::write(&path, json).unwrap();
+    path
+}
+
+fn autograder() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_autograder"))
+}
+
+/// Grades one of the fixtures under `tests/`, returning the exit code
+fn grade_fixture(name: &str) -> Option<i32> {
+    autograder()
+        .arg("--no-cache")
+        .current_dir(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("tests")
+                .join(name),
+        )
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap()
+        .code()
+}
+
+#[test]
+fn exit_code_says_how_grading_went() {
+    assert_eq!(grade_fixture("succeeding"), Some(0));
+    assert_eq!(grade_fixture("failing"), Some(1));
+
+    let config = write_config("malformed", r#"{"tests": "#);
+    let status = autograder()
+        .arg("--config")
+        .arg(&config)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .unwrap();
+    assert_eq!(status.code(), Some(2));
+    fs::remove_dir_all(config.parent().unwrap()).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn ctrl_c_kills_running_tests_and_exits_with_130() {
+    let config = write_config(
+        "interrupt",
+        r#"{"tests": [{"name": "hangs", "run": "sleep 60 & echo $! > sleeping.pid; wait", "timeout": "2m"}]}"#,
+    );
+    let dir = config.parent().unwrap();
+    let mut child = autograder()
+        .arg("--config")
+        .arg(&config)
+        .current_dir(dir)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let pid_file = dir.join("sleeping.pid");
+    let start = Instant::now();
+    let sleeping: libc::pid_t = loop {
+        if let Some(pid) = fs::read_to_string(&pid_file)
+            .ok()
+            .and_then(|text| text.trim().parse().ok())
+        {
+            break pid;
+        }
+        assert!(
+            start.elapsed() < Duration::from_secs(10),
+            "The test never started"
+        );
+        thread::sleep(Duration::from_millis(20));
+    };
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGINT) };
+    assert_eq!(child.wait().unwrap().code(), Some(130));
+
+    // The background sleep shares the test's process group, so it goes too
+    let start = Instant::now();
+    while unsafe { libc::kill(sleeping, 0) } == 0 {
+        assert!(
+            start.elapsed() < Duration::from_secs(5),
+            "The test is still running"
+        );
+        thread::sleep(Duration::from_millis(20));
+    }
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn failing_teardown_of_a_hidden_test_keeps_its_output_quiet() {
+    let config = write_config(
+        "hidden-teardown",
+        r#"{"tests": [{"name": "secret", "run": "true", "teardown": "echo the answer is 42; exit 1", "hidden": true}]}"#,
+    );
+    let output = autograder()
+        .arg("--no-cache")
+        .arg("--config")
+        .arg(&config)
+        .current_dir(config.parent().unwrap())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("The teardown of hidden test 'secret' failed"));
+    assert!(!stderr.contains("the answer is 42"));
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("the answer is 42"));
+    fs::remove_dir_all(config.parent().unwrap()).unwrap();
+}